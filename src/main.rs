@@ -1,14 +1,17 @@
 use std::{
-    ffi::{OsStr, OsString},
+    ffi::OsStr,
     fs::read_to_string,
     path::{Path, PathBuf},
     process::{exit, Command},
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
 
 use anyhow::{bail, Context, Result};
 use clap::Parser;
-use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError};
+use command_group::{CommandGroup, GroupChild};
+use crossbeam_channel::{unbounded, RecvTimeoutError, Sender};
+use file_id::FileId;
 use glob::{glob, Pattern};
 use ignore::WalkBuilder;
 use notify::{event::ModifyKind, Event, EventKind, RecursiveMode, Watcher};
@@ -26,10 +29,10 @@ fn should_event_trigger(event: &Event) -> bool {
     )
 }
 
-/// Run the specified command + args and log any errors that occur.
-#[allow(dead_code)]
-fn run_command(command: &OsStr, args: &[OsString]) {
-    let res = Command::new(command).args(args).status();
+/// Runs `command` to completion, logging a non-zero exit or launch failure in
+/// the same shape as the rest of the tool.
+fn run_to_completion(command: &mut Command) {
+    let res = command.status();
     match res.context("command failed to launch") {
         Ok(status) if status.success() => {}
         Ok(status) => match status.code() {
@@ -42,84 +45,129 @@ fn run_command(command: &OsStr, args: &[OsString]) {
     }
 }
 
-/// A helper struct to implement debouncing. It takes a [`Duration`] which
-/// indicates the time to wait after a new event before running the command.
-struct DebounceTimer {
-    start: Option<Instant>,
+/// What a [`Seedo`] worker does when a run is requested while a previous run is
+/// still in flight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunPolicy {
+    /// Run each request to completion, one after another.
+    Queue,
+    /// Run to completion, collapsing any requests that pile up during a run
+    /// into a single follow-up run.
+    Debounce,
+    /// Don't wait; kill the in-flight command's process group and respawn.
+    Restart,
+}
+
+/// Spawns the worker thread that owns `command` and executes it according to
+/// `policy`. The returned [`Sender`] is used by the main loop to request runs;
+/// dropping it tells the worker to exit.
+fn spawn_worker(mut command: Command, policy: RunPolicy) -> (Sender<()>, JoinHandle<()>) {
+    let (tx, rx) = unbounded::<()>();
+    let handle = thread::spawn(move || {
+        let mut child: Option<GroupChild> = None;
+        while rx.recv().is_ok() {
+            match policy {
+                RunPolicy::Queue => run_to_completion(&mut command),
+                RunPolicy::Debounce => {
+                    // Collapse any requests that queued up while we were busy
+                    // into this single run.
+                    while rx.try_recv().is_ok() {}
+                    run_to_completion(&mut command);
+                }
+                RunPolicy::Restart => {
+                    if let Some(mut running) = child.take() {
+                        if let Err(e) = running.kill() {
+                            error!("failed to kill running command: {:#}", e);
+                        }
+                        if let Err(e) = running.wait() {
+                            error!("failed to wait on killed command: {:#}", e);
+                        }
+                    }
+                    match command.group_spawn().context("command failed to launch") {
+                        Ok(spawned) => child = Some(spawned),
+                        Err(e) => error!("{:#}", e),
+                    }
+                }
+            }
+        }
+    });
+    (tx, handle)
+}
+
+/// Key under which a buffered event is stored. Paths that still exist are keyed
+/// by their stable file identity so repeated events and atomic-save renames
+/// collapse together; paths that have already disappeared (e.g. the source side
+/// of a rename, or a deletion) fall back to the path itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum EventKey {
+    Id(FileId),
+    Path(PathBuf),
+}
+
+/// A buffered, coalesced event and the instant at which it becomes eligible to
+/// flush.
+struct Pending {
+    event: Event,
+    deadline: Instant,
+}
+
+/// Debounces filesystem events keyed by file identity. Repeated events on the
+/// same file collapse onto a single entry, and atomic-save sequences (write to
+/// a temp file then rename over the target) that reuse one inode merge into one
+/// logical event. Buffered events are only released once the newest per-file
+/// deadline has elapsed, which suppresses double-fires.
+struct Debouncer {
     duration: Duration,
+    pending: std::collections::HashMap<EventKey, Pending>,
 }
 
-impl DebounceTimer {
-    /// Create a [`DebounceTimer`] struct. `duration` is the length of time to
-    /// debounce for when using the timer.
-    fn new(duration: Duration) -> DebounceTimer {
-        DebounceTimer {
-            start: None,
+impl Debouncer {
+    /// Create a [`Debouncer`]. `duration` is the quiet period that must elapse
+    /// after the last event touching a file before it is flushed.
+    fn new(duration: Duration) -> Debouncer {
+        Debouncer {
             duration,
+            pending: std::collections::HashMap::new(),
         }
     }
 
-    fn calculate_timeout(&self) -> Option<Duration> {
-        self.start
-            .map(|start| self.duration.saturating_sub(start.elapsed()))
+    /// Resolve the key for an event. Prefer a stable file id from whichever
+    /// path still exists (the destination of a rename resolves while the source
+    /// does not, so both sides coalesce), otherwise fall back to the path.
+    fn key_for(event: &Event) -> Option<EventKey> {
+        event
+            .paths
+            .iter()
+            .find_map(|p| file_id::get_file_id(p).ok().map(EventKey::Id))
+            .or_else(|| event.paths.first().map(|p| EventKey::Path(p.clone())))
     }
 
-    // /// This mimics the [`crossbeam_channel::Receiver::recv_timeout`] behavior
-    // /// except that it falls back to [`crossbeam_channel::Receiver::recv`] if
-    // /// the timer has not been started.
-    // fn timeout(&self, receiver: &Receiver<Event>) -> Result<Event, RecvTimeoutError> {
-    //     match self.calculate_timeout() {
-    //         Some(duration) => receiver.recv_timeout(duration),
-    //         None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
-    //     }
-    // }
-
-    /// Stops the timer.
-    fn expired(&self) -> bool {
-        match self.start {
-            Some(start) => Instant::now() > start + self.duration,
-            None => false,
+    /// Buffer `event`, (re)setting the deadline for the file it touches.
+    fn push(&mut self, event: Event) {
+        if let Some(key) = Debouncer::key_for(&event) {
+            let deadline = Instant::now() + self.duration;
+            self.pending.insert(key, Pending { event, deadline });
         }
     }
 
-    /// Stops the timer.
-    fn stop(&mut self) {
-        self.start = None;
+    /// Time until the newest buffered deadline elapses, or `None` when nothing
+    /// is buffered.
+    fn calculate_timeout(&self) -> Option<Duration> {
+        self.pending
+            .values()
+            .map(|p| p.deadline)
+            .max()
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
     }
 
-    /// Starts the timer if it wasn't previously started.
-    fn start_if_stopped(&mut self) {
-        if self.start.is_none() {
-            self.start = Some(Instant::now());
-        }
+    /// Whether there are buffered events whose newest deadline has elapsed.
+    fn ready(&self) -> bool {
+        self.calculate_timeout() == Some(Duration::ZERO)
     }
-}
-
-struct DebounceTimerSet {
-    timers: Vec<DebounceTimer>,
-}
 
-impl DebounceTimerSet {
-    fn calculate_timeout(&self) -> Option<Duration> {
-        let mut duration = None;
-        for timer in &self.timers {
-            if let Some(timer_duration) = timer.calculate_timeout() {
-                match &mut duration {
-                    Some(duration) => *duration = std::cmp::min(*duration, timer_duration),
-                    None => duration = Some(timer_duration),
-                }
-            };
-        }
-        duration
-    }
-    /// This mimics the [`crossbeam_channel::Receiver::recv_timeout`] behavior
-    /// except that it falls back to [`crossbeam_channel::Receiver::recv`] if
-    /// the timer has not been started.
-    fn timeout(&self, receiver: &Receiver<Event>) -> Result<Event, RecvTimeoutError> {
-        match self.calculate_timeout() {
-            Some(duration) => receiver.recv_timeout(duration),
-            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
-        }
+    /// Discard the buffered, coalesced events once they have been acted on.
+    fn flush(&mut self) {
+        self.pending.clear();
     }
 }
 
@@ -148,6 +196,35 @@ struct Opts {
     /// Debounce time in milliseconds
     #[clap(short, long = "debounce", default_value_t = 50)]
     debounce_ms: u64,
+    /// Kill and restart the command on each trigger instead of waiting for it
+    /// to finish. Useful for long-lived commands like dev servers.
+    #[clap(short, long)]
+    restart: bool,
+    /// Queue every trigger and run them to completion in order, instead of
+    /// collapsing triggers that arrive while a run is in flight
+    #[clap(short, long)]
+    queue: bool,
+    /// Clear the terminal each time a run is triggered. The clear happens when
+    /// the trigger fires; a run that has to wait for an in-flight run starts
+    /// later without a fresh clear
+    #[clap(short, long)]
+    clear: bool,
+    /// Run the command string through the platform shell (sh -c / cmd /C) so it
+    /// can contain pipelines, redirections, globs and `&&`
+    #[clap(short, long)]
+    shell: bool,
+    /// Shell program to use instead of the platform default (implies --shell)
+    #[clap(long, value_name = "SHELL")]
+    shell_program: Option<String>,
+    /// Number of threads to use for the initial tree walk
+    #[clap(short = 'j', long, default_value_t = default_threads())]
+    threads: usize,
+    /// Only trigger on files with these extensions (repeatable, e.g. -e rs -e toml)
+    #[clap(short = 'e', long = "ext")]
+    exts: Vec<String>,
+    /// Glob patterns to ignore even when they match a watch glob (repeatable)
+    #[clap(short = 'i', long = "ignore")]
+    ignore: Vec<String>,
     /// don't read .gitignore files
     #[clap(long)]
     skip_ignore_files: bool,
@@ -169,14 +246,77 @@ enum CommandToRun {
 }
 
 impl CommandToRun {
-    fn to_command(&self) -> Result<Command> {
+    fn to_command(&self, shell: &ShellConfig) -> Result<Command> {
         match self {
+            // In shell mode the arguments are a single command line; the CLI
+            // form arrives as a `Vec` (often a single quoted element), so
+            // rejoin them before handing the line to the shell.
+            CommandToRun::Vec(v) if shell.is_enabled() => {
+                Ok(shell_command(shell.program(), &v.join(" ")))
+            }
             CommandToRun::Vec(v) => command_from_iter(v),
+            CommandToRun::String(s) if shell.is_enabled() => {
+                Ok(shell_command(shell.program(), s))
+            }
             CommandToRun::String(s) => command_from_str(s),
         }
     }
 }
 
+/// How to interpret the command string: either as an explicit toggle or as the
+/// name of a shell program to invoke. In `seedo.toml` this can be written as
+/// `shell = true` or `shell = "bash"`.
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+enum ShellConfig {
+    Toggle(bool),
+    Program(String),
+}
+
+impl Default for ShellConfig {
+    fn default() -> ShellConfig {
+        ShellConfig::Toggle(false)
+    }
+}
+
+impl ShellConfig {
+    fn is_enabled(&self) -> bool {
+        matches!(self, ShellConfig::Toggle(true) | ShellConfig::Program(_))
+    }
+
+    /// The shell program to invoke, or `None` to use the platform default.
+    fn program(&self) -> Option<&str> {
+        match self {
+            ShellConfig::Program(p) => Some(p),
+            ShellConfig::Toggle(_) => None,
+        }
+    }
+}
+
+/// Builds a [`Command`] that runs `line` through a shell. `program` overrides
+/// the platform default (`sh` on Unix, `cmd` on Windows).
+fn shell_command(program: Option<&str>, line: &str) -> Command {
+    #[cfg(windows)]
+    {
+        // `cmd` takes `/C`; POSIX-style shells (bash, pwsh, ...) take `-c`.
+        let program = program.unwrap_or("cmd");
+        let is_cmd = Path::new(program)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .map(|s| s.eq_ignore_ascii_case("cmd"))
+            .unwrap_or(false);
+        let mut command = Command::new(program);
+        command.arg(if is_cmd { "/C" } else { "-c" }).arg(line);
+        command
+    }
+    #[cfg(not(windows))]
+    {
+        let mut command = Command::new(program.unwrap_or("sh"));
+        command.arg("-c").arg(line);
+        command
+    }
+}
+
 fn command_from_iter(iter: impl IntoIterator<Item = impl AsRef<OsStr>>) -> Result<Command> {
     let mut iter = iter.into_iter();
     let mut command = match iter.next() {
@@ -204,6 +344,13 @@ fn default_debounce_ms() -> u64 {
     50
 }
 
+/// Default number of walk threads: the available parallelism, mirroring fd.
+fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
 #[derive(Deserialize)]
 struct SeedoConfig {
     command_to_run: CommandToRun,
@@ -212,11 +359,57 @@ struct SeedoConfig {
     skip_ignore_files: bool,
     #[serde(default = "default_debounce_ms")]
     debounce_ms: u64,
+    #[serde(default)]
+    restart: bool,
+    #[serde(default)]
+    queue: bool,
+    #[serde(default)]
+    clear_screen: bool,
+    #[serde(default)]
+    shell: ShellConfig,
+    #[serde(default)]
+    exts: Vec<String>,
+    #[serde(default)]
+    ignore: Vec<String>,
 }
 
 struct Seedo {
-    command: Command,
     patterns: Vec<Pattern>,
+    /// Independent debouncer for this `Seedo`, so each config keeps its own
+    /// configured debounce window.
+    debouncer: Debouncer,
+    /// Channel used to signal the worker thread to run the command. The worker
+    /// owns the [`Command`] itself and applies the [`RunPolicy`].
+    run_tx: Sender<()>,
+    /// Handle to the worker thread; kept so it lives as long as the `Seedo`.
+    _worker: JoinHandle<()>,
+    /// When set, the terminal is cleared each time a run is requested from the
+    /// worker. A run that starts later, after an in-flight run, is not cleared
+    /// again.
+    clear_screen: bool,
+    /// Extension allowlist; when non-empty, only paths with one of these
+    /// extensions trigger a run.
+    exts: Vec<String>,
+    /// Globs that suppress a trigger even when a watch pattern matched.
+    ignore: Vec<Pattern>,
+}
+
+impl Seedo {
+    /// Whether a change to `path` should trigger this `Seedo`. A path must
+    /// match one of the watch patterns, carry an allowed extension (if an
+    /// allowlist is set), and not match any ignore glob.
+    fn should_trigger(&self, path: &Path) -> bool {
+        if !self.patterns.iter().any(|p| p.matches_path(path)) {
+            return false;
+        }
+        if !self.exts.is_empty() {
+            match path.extension().and_then(|e| e.to_str()) {
+                Some(ext) if self.exts.iter().any(|e| e == ext) => {}
+                _ => return false,
+            }
+        }
+        !self.ignore.iter().any(|p| p.matches_path(path))
+    }
 }
 
 // TODO glob executes before walkdir which reads gitignore
@@ -243,6 +436,15 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
             globs: opts.glob.clone(),
             skip_ignore_files: opts.skip_ignore_files,
             debounce_ms: opts.debounce_ms,
+            restart: opts.restart,
+            queue: opts.queue,
+            clear_screen: opts.clear,
+            shell: match opts.shell_program.clone() {
+                Some(program) => ShellConfig::Program(program),
+                None => ShellConfig::Toggle(opts.shell),
+            },
+            exts: opts.exts.clone(),
+            ignore: opts.ignore.clone(),
         });
     } else {
         let config_bytes = read_to_string(&opts.config)?;
@@ -251,9 +453,8 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
     }
 
     let mut seedos = vec![];
-    let mut debounce_timers = vec![];
     for config in &configs {
-        let command = config.command_to_run.to_command()?;
+        let command = config.command_to_run.to_command(&config.shell)?;
         let mut abs_globs = vec![];
         for glob in &config.globs {
             let p = Path::new(glob).absolutize()?;
@@ -278,48 +479,84 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
                 .git_global(false)
                 .git_exclude(false);
         }
-        for result in walk_builder.build() {
-            let entry = result?;
-            println!("watching '{}'", entry.path().display());
-            watcher.watch(entry.path(), RecursiveMode::NonRecursive)?;
+        // Walk the tree in parallel, funnelling matched paths back to the main
+        // thread which owns the (non-`Sync`) watcher and registers the watches.
+        let (path_snd, path_rcv) = unbounded::<PathBuf>();
+        let walker = walk_builder.threads(opts.threads).build_parallel();
+        let walk_handle = thread::spawn(move || {
+            walker.run(|| {
+                let path_snd = path_snd.clone();
+                Box::new(move |result| {
+                    if let Ok(entry) = result {
+                        let _ = path_snd.send(entry.path().to_path_buf());
+                    }
+                    ignore::WalkState::Continue
+                })
+            });
+        });
+        for path in path_rcv {
+            debug!("watching '{}'", path.display());
+            if let Err(e) = watcher.watch(&path, RecursiveMode::NonRecursive) {
+                error!("Failed to watch {}: {:#}", path.display(), e);
+            }
         }
+        let _ = walk_handle.join();
 
         let mut patterns = vec![];
         for glob in abs_globs {
             patterns.push(Pattern::new(&glob)?);
         }
 
-        debounce_timers.push(DebounceTimer::new(Duration::from_millis(
-            config.debounce_ms,
-        )));
-        seedos.push(Seedo { command, patterns });
-    }
+        // Ignore globs are absolutized like the watch globs so they compare
+        // against the same absolute event paths.
+        let mut ignore = vec![];
+        for glob in &config.ignore {
+            let p = Path::new(glob).absolutize()?;
+            ignore.push(Pattern::new(&p.to_string_lossy())?);
+        }
 
-    let mut debounce_timer = DebounceTimerSet {
-        timers: debounce_timers,
-    };
+        let policy = if config.restart {
+            RunPolicy::Restart
+        } else if config.queue {
+            RunPolicy::Queue
+        } else {
+            RunPolicy::Debounce
+        };
+        let (run_tx, worker) = spawn_worker(command, policy);
+        seedos.push(Seedo {
+            patterns,
+            debouncer: Debouncer::new(Duration::from_millis(config.debounce_ms)),
+            run_tx,
+            _worker: worker,
+            clear_screen: config.clear_screen,
+            exts: config.exts.clone(),
+            ignore,
+        });
+    }
 
     loop {
-        match debounce_timer.timeout(&rcv) {
+        // Wake up for the soonest pending deadline across all `Seedo`s, or block
+        // indefinitely when nothing is buffered.
+        let timeout = seedos
+            .iter()
+            .filter_map(|seedo| seedo.debouncer.calculate_timeout())
+            .min();
+        let received = match timeout {
+            Some(duration) => rcv.recv_timeout(duration),
+            None => rcv.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
             Ok(event) => {
                 debug!("{:?}", event);
                 // We need to watch newly created files for changes.
                 if let EventKind::Create(_) = event.kind {
                     watch_new_files(&mut watcher, &event);
                 }
-                'outer: for (timer, seedo) in
-                    debounce_timer.timers.iter_mut().zip(seedos.iter_mut())
-                {
-                    for path in &event.paths {
-                        println!("event path {}", path.display());
-                        for pattern in &seedo.patterns {
-                            println!("pattern {}", pattern);
-                            if pattern.matches_path(path) {
-                                println!("start if stopped on {:?}", seedo.command);
-                                timer.start_if_stopped();
-                                continue 'outer;
-                            }
-                        }
+                // Buffer the event in every `Seedo` it would trigger; each keeps
+                // its own deadline.
+                for seedo in seedos.iter_mut() {
+                    if event.paths.iter().any(|path| seedo.should_trigger(path)) {
+                        seedo.debouncer.push(event.clone());
                     }
                 }
                 continue;
@@ -334,20 +571,19 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
                 debug!("timeout reached. running command");
             }
         };
-        for (timer, seedo) in debounce_timer.timers.iter_mut().zip(seedos.iter_mut()) {
-            if timer.expired() {
-                timer.stop();
-                let res = seedo.command.status();
-                match res.context("command failed to launch") {
-                    Ok(status) if status.success() => {}
-                    Ok(status) => match status.code() {
-                        Some(code) => error!("command exited with code = {code}"),
-                        None => error!("command exited without code"),
-                    },
-                    Err(e) => {
-                        error!("{:#}", e);
+        for seedo in seedos.iter_mut() {
+            if seedo.debouncer.ready() {
+                seedo.debouncer.flush();
+                if seedo.clear_screen {
+                    if let Err(e) = clearscreen::clear() {
+                        warn!("failed to clear terminal: {:#}", e);
                     }
                 }
+                // Hand the run off to the worker thread so the main loop can
+                // keep matching events while the command runs.
+                if seedo.run_tx.send(()).is_err() {
+                    error!("worker thread has gone away");
+                }
             }
         }
     }